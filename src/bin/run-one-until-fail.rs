@@ -1,20 +1,19 @@
 use std::{collections::VecDeque, error::Error};
 
-use run_one::{parse_args, run};
+use run_one::{parse_args, run_loop, wait_for_dependencies};
 
 pub fn main() -> Result<(), Box<dyn Error>> {
     let args: VecDeque<String> = std::env::args().collect();
     let cmd = parse_args(args, std::env::vars())?;
 
-    loop {
-        match run(&cmd) {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Error: {e}");
-                break;
-            }
-        }
+    wait_for_dependencies(&cmd)?;
+
+    let summary = run_loop(&cmd);
+
+    if let Err(e) = &summary.result {
+        eprintln!("Error: {e}");
     }
+    println!("stopped after {} runs", summary.runs);
 
     print!("\x07");
 
@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::{anyhow, Result};
+
+use crate::Cmd;
+
+/// Default size of the ring buffer kept for `RUN_ONE_CAPTURE`'s failure tail
+/// when `RUN_ONE_CAPTURE_LINES` is not set.
+const DEFAULT_CAPTURE_LINES: usize = 200;
+
+/// Runs `command` with stdout/stderr piped, streaming both through to the
+/// terminal while also keeping a ring buffer of the last `cmd.capture_lines`
+/// lines. On failure the tail is flushed to stderr; the full output is
+/// additionally written to `cmd.log_file` if set.
+pub(crate) fn run_with_capture(command: &mut Command, cmd: &Cmd) -> Result<ExitStatus> {
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| anyhow!("Failed to execute command: {}", e))?;
+
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+
+    let (tx, rx) = mpsc::channel();
+    let stdout_thread = spawn_reader(stdout, std::io::stdout(), tx.clone());
+    let stderr_thread = spawn_reader(stderr, std::io::stderr(), tx);
+
+    let max_lines = cmd.capture_lines.unwrap_or(DEFAULT_CAPTURE_LINES);
+    let mut tail: VecDeque<String> = VecDeque::with_capacity(max_lines);
+    let mut full_output = String::new();
+
+    for line in rx {
+        if cmd.log_file.is_some() {
+            full_output.push_str(&line);
+            full_output.push('\n');
+        }
+        record_line(&mut tail, max_lines, line);
+    }
+
+    let _ = stdout_thread.join();
+    let _ = stderr_thread.join();
+
+    let status = child
+        .wait()
+        .map_err(|e| anyhow!("Failed to execute command: {}", e))?;
+
+    if let Some(log_file) = &cmd.log_file {
+        if let Err(e) = std::fs::write(log_file, &full_output) {
+            eprintln!(
+                "Failed to write RUN_ONE_LOG_FILE `{}`: {e}",
+                log_file.display()
+            );
+        }
+    }
+
+    if !status.success() {
+        eprintln!("--- last {} line(s) of output ---", tail.len());
+        for line in &tail {
+            eprintln!("{line}");
+        }
+    }
+
+    Ok(status)
+}
+
+/// Pushes `line` onto `tail`, evicting from the front until at most
+/// `max_lines` remain (so `max_lines == 0` keeps the buffer empty).
+fn record_line(tail: &mut VecDeque<String>, max_lines: usize, line: String) {
+    tail.push_back(line);
+    while tail.len() > max_lines {
+        tail.pop_front();
+    }
+}
+
+/// Copies `reader` line-by-line to `passthrough` and sends each line down
+/// `tx`, on its own thread so a full pipe buffer on one stream can't stall
+/// the other. Reads raw bytes via `read_until` rather than `BufRead::lines`
+/// so a non-UTF-8 byte in the child's output doesn't stop the thread (and
+/// thus the pipe drain) dead.
+fn spawn_reader<R, W>(reader: R, mut passthrough: W, tx: mpsc::Sender<String>) -> thread::JoinHandle<()>
+where
+    R: std::io::Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let _ = passthrough.write_all(&buf);
+                    let line = String::from_utf8_lossy(&buf)
+                        .trim_end_matches(['\n', '\r'])
+                        .to_string();
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_cmd(cmd: &str, args: &[&str]) -> Cmd {
+        Cmd {
+            cmd: cmd.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            capture: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn captures_successful_output() {
+        let cmd = base_cmd("echo", &["hello"]);
+        let mut command = Command::new(&cmd.cmd);
+        command.args(&cmd.args);
+
+        let status = run_with_capture(&mut command, &cmd).unwrap();
+
+        assert!(status.success());
+    }
+
+    #[test]
+    fn ring_buffer_keeps_only_the_last_n_lines() {
+        let mut tail = VecDeque::new();
+        for line in ["one", "two", "three"] {
+            record_line(&mut tail, 2, line.to_string());
+        }
+
+        assert_eq!(
+            tail,
+            VecDeque::from(vec!["two".to_string(), "three".to_string()])
+        );
+    }
+
+    #[test]
+    fn ring_buffer_with_zero_max_lines_keeps_nothing() {
+        let mut tail = VecDeque::new();
+        record_line(&mut tail, 0, "one".to_string());
+        record_line(&mut tail, 0, "two".to_string());
+
+        assert!(tail.is_empty());
+    }
+
+    #[test]
+    fn writes_full_output_to_log_file() {
+        let log_file = std::env::temp_dir().join("run-one-capture-test.log");
+        let cmd = Cmd {
+            log_file: Some(log_file.clone()),
+            ..base_cmd("echo", &["hello from capture test"])
+        };
+        let mut command = Command::new(&cmd.cmd);
+        command.args(&cmd.args);
+
+        run_with_capture(&mut command, &cmd).unwrap();
+
+        let contents = std::fs::read_to_string(&log_file).unwrap();
+        assert!(contents.contains("hello from capture test"));
+
+        std::fs::remove_file(&log_file).ok();
+    }
+
+    #[test]
+    fn failing_command_still_reports_exit_status() {
+        let cmd = base_cmd("false", &[]);
+        let mut command = Command::new(&cmd.cmd);
+        command.args(&cmd.args);
+
+        let status = run_with_capture(&mut command, &cmd).unwrap();
+
+        assert!(!status.success());
+    }
+}
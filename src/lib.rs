@@ -1,15 +1,38 @@
-use std::{collections::VecDeque, process::Command};
+mod capture;
+mod readiness;
+mod retry;
+mod tokenizer;
+
+use std::{collections::VecDeque, path::PathBuf, process::Command};
 
 use anyhow::{anyhow, Result};
 
-#[derive(Debug)]
+pub use readiness::wait_for_dependencies;
+pub use retry::{run_loop, Backoff, RunSummary, StopCondition};
+
+#[derive(Debug, Default)]
 pub struct Cmd {
     cmd: String,
     args: Vec<String>,
     wait: Option<u64>,
+    stop_condition: StopCondition,
+    max_runs: Option<u64>,
+    backoff: Backoff,
+    max_wait: Option<u64>,
+    backoff_jitter: bool,
+    cwd: Option<PathBuf>,
+    env_overrides: Vec<(String, String)>,
+    env_clear: bool,
+    wait_hosts: Vec<String>,
+    wait_paths: Vec<PathBuf>,
+    wait_timeout: Option<u64>,
+    wait_interval: Option<u64>,
+    capture: bool,
+    capture_lines: Option<usize>,
+    log_file: Option<PathBuf>,
 }
 
-pub fn parse_args<I>(mut args: VecDeque<String>, mut vars: I) -> Result<Cmd>
+pub fn parse_args<I>(mut args: VecDeque<String>, vars: I) -> Result<Cmd>
 where
     I: Iterator<Item = (String, String)>,
 {
@@ -19,14 +42,50 @@ where
         None => return Err(anyhow!("Unable to get the name of the program.")),
     };
 
-    let cmd = match args.pop_front() {
-        Some(cmd) => cmd,
-        None => return Err(anyhow!("Unable to get the command.")),
+    let vars: Vec<(String, String)> = vars.collect();
+    let env = |key: &str| -> bool {
+        vars.iter()
+            .any(|(k, v)| k == key && v != "0" && !v.is_empty())
+    };
+
+    let shell_flag = args.front().map(|a| a == "--shell").unwrap_or(false);
+    if shell_flag {
+        args.pop_front();
+    }
+
+    let (cmd, args) = if shell_flag || env("RUN_ONE_SHELL") {
+        let command_line = match args.pop_front() {
+            Some(s) => s,
+            None => return Err(anyhow!("Unable to get the command.")),
+        };
+        let mut words = tokenizer::tokenize(&command_line)?.into_iter();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => return Err(anyhow!("Unable to get the command.")),
+        };
+        (cmd, words.collect())
+    } else if env("RUN_ONE_SYSTEM_SHELL") {
+        let command_line = match args.pop_front() {
+            Some(s) => s,
+            None => return Err(anyhow!("Unable to get the command.")),
+        };
+        if cfg!(windows) {
+            ("cmd".to_string(), vec!["/C".to_string(), command_line])
+        } else {
+            ("sh".to_string(), vec!["-c".to_string(), command_line])
+        }
+    } else {
+        let cmd = match args.pop_front() {
+            Some(cmd) => cmd,
+            None => return Err(anyhow!("Unable to get the command.")),
+        };
+        (cmd, args.into_iter().collect())
     };
 
     let wait = vars
+        .iter()
         .find(|(key, _)| key == "RUN_ONE_WAIT")
-        .map(|(_, val)| val);
+        .map(|(_, val)| val.clone());
     let wait = wait.and_then(|val| match val.parse::<u64>() {
         Ok(val) => Some(val),
         Err(e) => {
@@ -35,33 +94,212 @@ where
         }
     });
 
+    let stop_condition = match vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_STOP_CONDITION")
+        .map(|(_, val)| val.as_str())
+    {
+        Some("until-success") => StopCondition::UntilSuccess,
+        Some("until-failure") | None => StopCondition::UntilFailure,
+        Some(other) => {
+            eprintln!("Invalid value for RUN_ONE_STOP_CONDITION: {other}");
+            StopCondition::UntilFailure
+        }
+    };
+
+    let max_runs = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_MAX_RUNS")
+        .map(|(_, val)| val.clone());
+    let max_runs = max_runs.and_then(|val| match val.parse::<u64>() {
+        Ok(val) => Some(val),
+        Err(e) => {
+            eprintln!("Invalid value for RUN_ONE_MAX_RUNS: {e}");
+            None
+        }
+    });
+
+    let backoff = match vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_BACKOFF")
+        .map(|(_, val)| val.as_str())
+    {
+        Some("exponential") => Backoff::Exponential,
+        Some("fixed") | None => Backoff::Fixed,
+        Some(other) => {
+            eprintln!("Invalid value for RUN_ONE_BACKOFF: {other}");
+            Backoff::Fixed
+        }
+    };
+
+    let max_wait = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_MAX_WAIT")
+        .map(|(_, val)| val.clone());
+    let max_wait = max_wait.and_then(|val| match val.parse::<u64>() {
+        Ok(val) => Some(val),
+        Err(e) => {
+            eprintln!("Invalid value for RUN_ONE_MAX_WAIT: {e}");
+            None
+        }
+    });
+
+    let backoff_jitter = env("RUN_ONE_BACKOFF_JITTER");
+
+    let cwd = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_CWD")
+        .map(|(_, val)| PathBuf::from(val));
+
+    let mut env_overrides: Vec<(String, String)> = vars
+        .iter()
+        .filter(|(key, _)| key.starts_with("RUN_ONE_ENV_") && key != "RUN_ONE_ENV_CLEAR")
+        .map(|(key, val)| {
+            (
+                key.trim_start_matches("RUN_ONE_ENV_").to_string(),
+                val.clone(),
+            )
+        })
+        .collect();
+    if let Some((_, list)) = vars.iter().find(|(key, _)| key == "RUN_ONE_ENV") {
+        for pair in list.split(',') {
+            if let Some((key, val)) = pair.split_once('=') {
+                env_overrides.push((key.trim().to_string(), val.trim().to_string()));
+            }
+        }
+    }
+
+    let env_clear = env("RUN_ONE_ENV_CLEAR");
+
+    let wait_hosts = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_WAIT_HOSTS")
+        .map(|(_, val)| split_csv(val))
+        .unwrap_or_default();
+
+    let wait_paths = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_WAIT_PATHS")
+        .map(|(_, val)| split_csv(val).into_iter().map(PathBuf::from).collect())
+        .unwrap_or_default();
+
+    let wait_timeout = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_WAIT_TIMEOUT")
+        .map(|(_, val)| val.clone());
+    let wait_timeout = wait_timeout.and_then(|val| match val.parse::<u64>() {
+        Ok(val) => Some(val),
+        Err(e) => {
+            eprintln!("Invalid value for RUN_ONE_WAIT_TIMEOUT: {e}");
+            None
+        }
+    });
+
+    let wait_interval = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_WAIT_INTERVAL")
+        .map(|(_, val)| val.clone());
+    let wait_interval = wait_interval.and_then(|val| match val.parse::<u64>() {
+        Ok(val) => Some(val),
+        Err(e) => {
+            eprintln!("Invalid value for RUN_ONE_WAIT_INTERVAL: {e}");
+            None
+        }
+    });
+
+    let capture = env("RUN_ONE_CAPTURE");
+
+    let capture_lines = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_CAPTURE_LINES")
+        .map(|(_, val)| val.clone());
+    let capture_lines = capture_lines.and_then(|val| match val.parse::<usize>() {
+        Ok(val) => Some(val),
+        Err(e) => {
+            eprintln!("Invalid value for RUN_ONE_CAPTURE_LINES: {e}");
+            None
+        }
+    });
+
+    let log_file = vars
+        .iter()
+        .find(|(key, _)| key == "RUN_ONE_LOG_FILE")
+        .map(|(_, val)| PathBuf::from(val));
+
     Ok(Cmd {
         cmd,
-        args: args.into_iter().collect(),
+        args,
         wait,
+        stop_condition,
+        max_runs,
+        backoff,
+        max_wait,
+        backoff_jitter,
+        cwd,
+        env_overrides,
+        env_clear,
+        wait_hosts,
+        wait_paths,
+        wait_timeout,
+        wait_interval,
+        capture,
+        capture_lines,
+        log_file,
     })
 }
 
 pub fn run(cmd: &Cmd) -> Result<()> {
-    let cmd_res = Command::new(&cmd.cmd).args(&cmd.args).spawn();
-
-    let r = match cmd_res {
-        Ok(mut child) => {
-            let status = child.wait().unwrap();
-            if !status.success() {
-                Err(anyhow!("Command failed with exit code: {}", status))
-            } else {
-                Ok(())
-            }
+    let mut command = Command::new(&cmd.cmd);
+    command.args(&cmd.args);
+
+    if let Some(cwd) = &cmd.cwd {
+        command.current_dir(cwd);
+    }
+    if cmd.env_clear {
+        command.env_clear();
+    }
+    command.envs(cmd.env_overrides.iter().cloned());
+
+    let status = if cmd.capture {
+        capture::run_with_capture(&mut command, cmd)?
+    } else {
+        match command.spawn() {
+            Ok(mut child) => child.wait().unwrap(),
+            Err(e) => return Err(anyhow!("Failed to execute command: {}", e)),
         }
-        Err(e) => Err(anyhow!("Failed to execute command: {}", e)),
     };
 
-    if let Some(wait) = cmd.wait {
-        std::thread::sleep(std::time::Duration::from_secs(wait));
+    if !status.success() {
+        Err(anyhow!(
+            "Command `{}` (running in folder `{}`) exited with status {}",
+            command_line(cmd),
+            resolved_cwd(cmd).display(),
+            status
+        ))
+    } else {
+        Ok(())
     }
+}
+
+fn command_line(cmd: &Cmd) -> String {
+    std::iter::once(cmd.cmd.clone())
+        .chain(cmd.args.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn resolved_cwd(cmd: &Cmd) -> PathBuf {
+    cmd.cwd
+        .clone()
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+}
 
-    r
+fn split_csv(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
 #[cfg(test)]
@@ -79,6 +317,14 @@ mod tests {
             .into_iter()
     }
 
+    fn test_cmd(cmd: &str, args: &[&str]) -> Cmd {
+        Cmd {
+            cmd: cmd.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_parse_args_simple_command() {
         let args = make_args(&["run-one", "echo", "hello"]);
@@ -154,6 +400,187 @@ mod tests {
             .contains("Unable to get the name of the program"));
     }
 
+    #[test]
+    fn test_parse_args_with_shell_flag_tokenizes_command_string() {
+        let args = make_args(&["run-one", "--shell", "echo 'hello world'"]);
+        let vars = make_vars(&[]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.cmd, "echo");
+        assert_eq!(cmd.args, vec!["hello world"]);
+    }
+
+    #[test]
+    fn test_parse_args_with_run_one_shell_env_tokenizes_command_string() {
+        let args = make_args(&["run-one", "echo foo | sed 's/foo/bar/g'"]);
+        let vars = make_vars(&[("RUN_ONE_SHELL", "1")]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.cmd, "echo");
+        assert_eq!(cmd.args, vec!["foo", "|", "sed", "s/foo/bar/g"]);
+    }
+
+    #[test]
+    fn test_parse_args_with_unterminated_quote_is_an_error() {
+        let args = make_args(&["run-one", "--shell", "echo 'unterminated"]);
+        let vars = make_vars(&[]);
+
+        let result = parse_args(args, vars);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_args_with_system_shell_env_spawns_via_sh() {
+        let args = make_args(&["run-one", "echo foo | sed 's/foo/bar/g'"]);
+        let vars = make_vars(&[("RUN_ONE_SYSTEM_SHELL", "1")]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.cmd, "sh");
+        assert_eq!(cmd.args, vec!["-c", "echo foo | sed 's/foo/bar/g'"]);
+    }
+
+    #[test]
+    fn test_parse_args_with_retry_policy_env_vars() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[
+            ("RUN_ONE_STOP_CONDITION", "until-success"),
+            ("RUN_ONE_MAX_RUNS", "3"),
+            ("RUN_ONE_BACKOFF", "exponential"),
+            ("RUN_ONE_MAX_WAIT", "60"),
+        ]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.stop_condition, StopCondition::UntilSuccess);
+        assert_eq!(cmd.max_runs, Some(3));
+        assert_eq!(cmd.backoff, Backoff::Exponential);
+        assert_eq!(cmd.max_wait, Some(60));
+    }
+
+    #[test]
+    fn test_parse_args_defaults_retry_policy() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.stop_condition, StopCondition::UntilFailure);
+        assert_eq!(cmd.max_runs, None);
+        assert_eq!(cmd.backoff, Backoff::Fixed);
+    }
+
+    #[test]
+    fn test_parse_args_with_cwd_env_var() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[("RUN_ONE_CWD", "/tmp")]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.cwd, Some(PathBuf::from("/tmp")));
+    }
+
+    #[test]
+    fn test_parse_args_with_individual_env_override_vars() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[("RUN_ONE_ENV_FOO", "bar"), ("RUN_ONE_ENV_BAZ", "qux")]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert!(cmd
+            .env_overrides
+            .contains(&("FOO".to_string(), "bar".to_string())));
+        assert!(cmd
+            .env_overrides
+            .contains(&("BAZ".to_string(), "qux".to_string())));
+    }
+
+    #[test]
+    fn test_parse_args_with_env_list_var() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[("RUN_ONE_ENV", "FOO=bar,BAZ=qux")]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert!(cmd
+            .env_overrides
+            .contains(&("FOO".to_string(), "bar".to_string())));
+        assert!(cmd
+            .env_overrides
+            .contains(&("BAZ".to_string(), "qux".to_string())));
+    }
+
+    #[test]
+    fn test_parse_args_with_env_clear() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[("RUN_ONE_ENV_CLEAR", "1")]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert!(cmd.env_clear);
+        assert!(cmd.env_overrides.is_empty());
+    }
+
+    #[test]
+    fn test_parse_args_with_readiness_env_vars() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[
+            ("RUN_ONE_WAIT_HOSTS", "db:5432, cache:6379"),
+            ("RUN_ONE_WAIT_PATHS", "/tmp/ready.flag"),
+            ("RUN_ONE_WAIT_TIMEOUT", "60"),
+            ("RUN_ONE_WAIT_INTERVAL", "2"),
+        ]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert_eq!(cmd.wait_hosts, vec!["db:5432", "cache:6379"]);
+        assert_eq!(cmd.wait_paths, vec![PathBuf::from("/tmp/ready.flag")]);
+        assert_eq!(cmd.wait_timeout, Some(60));
+        assert_eq!(cmd.wait_interval, Some(2));
+    }
+
+    #[test]
+    fn test_parse_args_defaults_readiness_to_empty() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert!(cmd.wait_hosts.is_empty());
+        assert!(cmd.wait_paths.is_empty());
+    }
+
+    #[test]
+    fn test_parse_args_with_capture_env_vars() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[
+            ("RUN_ONE_CAPTURE", "1"),
+            ("RUN_ONE_CAPTURE_LINES", "50"),
+            ("RUN_ONE_LOG_FILE", "/tmp/run-one.log"),
+        ]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert!(cmd.capture);
+        assert_eq!(cmd.capture_lines, Some(50));
+        assert_eq!(cmd.log_file, Some(PathBuf::from("/tmp/run-one.log")));
+    }
+
+    #[test]
+    fn test_parse_args_defaults_capture_to_disabled() {
+        let args = make_args(&["run-one", "echo", "test"]);
+        let vars = make_vars(&[]);
+
+        let cmd = parse_args(args, vars).unwrap();
+
+        assert!(!cmd.capture);
+        assert_eq!(cmd.capture_lines, None);
+        assert_eq!(cmd.log_file, None);
+    }
+
     #[test]
     fn test_parse_args_multiple_arguments() {
         let args = make_args(&["run-one", "git", "commit", "-m", "test message"]);
@@ -167,11 +594,7 @@ mod tests {
 
     #[test]
     fn test_run_successful_command() {
-        let cmd = Cmd {
-            cmd: "true".to_string(),
-            args: vec![],
-            wait: None,
-        };
+        let cmd = test_cmd("true", &[]);
 
         let result = run(&cmd);
 
@@ -180,28 +603,51 @@ mod tests {
 
     #[test]
     fn test_run_failing_command() {
+        let cmd = test_cmd("false", &[]);
+
+        let result = run(&cmd);
+
+        assert!(result.is_err());
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("Command `false`"));
+        assert!(message.contains("exited with status"));
+    }
+
+    #[test]
+    fn test_run_applies_cwd_and_env_overrides_to_the_child() {
+        let dir = std::env::temp_dir();
         let cmd = Cmd {
-            cmd: "false".to_string(),
-            args: vec![],
-            wait: None,
+            cwd: Some(dir.clone()),
+            env_overrides: vec![("RUN_ONE_TEST_VAR".to_string(), "1".to_string())],
+            ..test_cmd("sh", &["-c", "[ \"$RUN_ONE_TEST_VAR\" = \"1\" ]"])
         };
 
         let result = run(&cmd);
 
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Command failed"));
+        assert!(result.is_ok());
     }
 
     #[test]
-    fn test_run_nonexistent_command() {
+    fn test_run_failure_message_includes_cwd_and_full_command_line() {
+        let dir = std::env::temp_dir();
         let cmd = Cmd {
-            cmd: "nonexistent_command_12345".to_string(),
-            args: vec![],
-            wait: None,
+            cwd: Some(dir.clone()),
+            ..test_cmd("false", &[])
         };
 
         let result = run(&cmd);
 
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains(&dir.display().to_string()));
+        assert!(message.contains("Command `false`"));
+    }
+
+    #[test]
+    fn test_run_nonexistent_command() {
+        let cmd = test_cmd("nonexistent_command_12345", &[]);
+
+        let result = run(&cmd);
+
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -211,11 +657,7 @@ mod tests {
 
     #[test]
     fn test_run_command_with_arguments() {
-        let cmd = Cmd {
-            cmd: "echo".to_string(),
-            args: vec!["hello".to_string(), "world".to_string()],
-            wait: None,
-        };
+        let cmd = test_cmd("echo", &["hello", "world"]);
 
         let result = run(&cmd);
 
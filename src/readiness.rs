@@ -0,0 +1,114 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::Cmd;
+
+/// Default timeout for the whole readiness phase when `RUN_ONE_WAIT_TIMEOUT`
+/// is not set.
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Default delay between readiness passes when `RUN_ONE_WAIT_INTERVAL` is
+/// not set.
+const DEFAULT_INTERVAL_SECS: u64 = 1;
+/// How long a single `TcpStream::connect` attempt is allowed to take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Blocks until every host in `cmd.wait_hosts` accepts a TCP connection and
+/// every path in `cmd.wait_paths` exists, or returns an error once
+/// `cmd.wait_timeout` elapses. Runs once, before the retry loop starts; it
+/// is unrelated to the post-run `wait` sleep.
+pub fn wait_for_dependencies(cmd: &Cmd) -> Result<()> {
+    if cmd.wait_hosts.is_empty() && cmd.wait_paths.is_empty() {
+        return Ok(());
+    }
+
+    let timeout = Duration::from_secs(cmd.wait_timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let interval = Duration::from_secs(cmd.wait_interval.unwrap_or(DEFAULT_INTERVAL_SECS));
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if all_dependencies_ready(cmd) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "Timed out after {}s waiting for dependencies to become ready",
+                timeout.as_secs()
+            ));
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn all_dependencies_ready(cmd: &Cmd) -> bool {
+    cmd.wait_hosts.iter().all(|host| host_is_ready(host))
+        && cmd.wait_paths.iter().all(|path| path.exists())
+}
+
+fn host_is_ready(host_port: &str) -> bool {
+    match host_port.to_socket_addrs() {
+        Ok(addrs) => addrs
+            .into_iter()
+            .any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok()),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn base_cmd() -> Cmd {
+        Cmd {
+            cmd: "true".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_dependencies_returns_immediately() {
+        let cmd = base_cmd();
+
+        assert!(wait_for_dependencies(&cmd).is_ok());
+    }
+
+    #[test]
+    fn succeeds_once_the_path_exists() {
+        let cmd = Cmd {
+            wait_paths: vec![std::env::temp_dir()],
+            ..base_cmd()
+        };
+
+        assert!(wait_for_dependencies(&cmd).is_ok());
+    }
+
+    #[test]
+    fn succeeds_once_the_host_accepts_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let cmd = Cmd {
+            wait_hosts: vec![addr.to_string()],
+            ..base_cmd()
+        };
+
+        assert!(wait_for_dependencies(&cmd).is_ok());
+    }
+
+    #[test]
+    fn times_out_when_a_path_never_appears() {
+        let cmd = Cmd {
+            wait_paths: vec!["/no/such/path/run-one-readiness-test".into()],
+            wait_timeout: Some(0),
+            wait_interval: Some(0),
+            ..base_cmd()
+        };
+
+        let result = wait_for_dependencies(&cmd);
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Timed out"));
+    }
+}
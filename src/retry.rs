@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::{run, Cmd};
+
+/// When the retry loop in [`run_loop`] should stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StopCondition {
+    /// Keep running while the command succeeds; stop on the first failure.
+    /// This is the historical `run-one-until-fail` behavior.
+    #[default]
+    UntilFailure,
+    /// Keep running while the command fails; stop on the first success.
+    UntilSuccess,
+}
+
+/// How long to sleep between iterations of [`run_loop`]. With
+/// `backoff_jitter` set, either strategy's sleep is further varied by
+/// [`jitter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backoff {
+    /// Always sleep `cmd.wait` seconds.
+    #[default]
+    Fixed,
+    /// Sleep `min(wait * 2^(n-1), max_wait)` seconds after iteration `n`.
+    Exponential,
+}
+
+/// Outcome of [`run_loop`]: how many times the command was run, and the
+/// result of the last iteration.
+#[derive(Debug)]
+pub struct RunSummary {
+    pub runs: u64,
+    pub result: Result<()>,
+}
+
+/// Runs `cmd` repeatedly according to its retry policy (stop condition,
+/// `max_runs` cap and backoff strategy), sleeping between iterations.
+pub fn run_loop(cmd: &Cmd) -> RunSummary {
+    let mut runs: u64 = 0;
+
+    loop {
+        runs += 1;
+        let result = run(cmd);
+
+        let should_stop = match cmd.stop_condition {
+            StopCondition::UntilFailure => result.is_err(),
+            StopCondition::UntilSuccess => result.is_ok(),
+        };
+        let hit_max_runs = cmd.max_runs.is_some_and(|max| runs >= max);
+
+        if should_stop || hit_max_runs {
+            return RunSummary { runs, result };
+        }
+
+        if let Some(sleep) = backoff_duration(cmd, runs) {
+            std::thread::sleep(sleep);
+        }
+    }
+}
+
+fn backoff_duration(cmd: &Cmd, iteration: u64) -> Option<Duration> {
+    let base = cmd.wait?;
+
+    let seconds = match cmd.backoff {
+        Backoff::Fixed => base,
+        Backoff::Exponential => {
+            let shift = iteration.saturating_sub(1).min(63) as u32;
+            let scaled = base.checked_mul(1u64 << shift).unwrap_or(u64::MAX);
+            match cmd.max_wait {
+                Some(max_wait) => scaled.min(max_wait),
+                None => scaled,
+            }
+        }
+    };
+
+    Some(Duration::from_secs(jitter(
+        seconds,
+        cmd.backoff_jitter,
+        iteration,
+        process_jitter_seed(),
+    )))
+}
+
+/// Applies up to +/-10% jitter to `seconds`, but never less than +/-1
+/// second, so waits under 10 seconds vary by more than 10%. `seed` supplies
+/// the entropy:
+/// production code derives it fresh from the clock and process id via
+/// [`process_jitter_seed`] so concurrent `run-one` processes don't retry in
+/// lockstep, while tests pass a fixed constant to stay deterministic.
+fn jitter(seconds: u64, enabled: bool, iteration: u64, seed: u64) -> u64 {
+    if !enabled || seconds == 0 {
+        return seconds;
+    }
+
+    let sample = seed
+        .wrapping_add(iteration)
+        .wrapping_mul(2_654_435_761);
+    let spread = (seconds / 10).max(1);
+    let offset = (sample % (2 * spread + 1)) as i64 - spread as i64;
+
+    (seconds as i64 + offset).max(0) as u64
+}
+
+/// Per-process entropy source for [`jitter`]: the wall clock combined with
+/// the process id, so that two processes started in the same instant still
+/// diverge.
+fn process_jitter_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+
+    nanos ^ (std::process::id() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_cmd(cmd: &str) -> Cmd {
+        Cmd {
+            cmd: cmd.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stops_after_first_failure_by_default() {
+        let cmd = base_cmd("false");
+
+        let summary = run_loop(&cmd);
+
+        assert_eq!(summary.runs, 1);
+        assert!(summary.result.is_err());
+    }
+
+    #[test]
+    fn until_success_keeps_running_until_a_success() {
+        let cmd = Cmd {
+            stop_condition: StopCondition::UntilSuccess,
+            max_runs: Some(3),
+            ..base_cmd("false")
+        };
+
+        let summary = run_loop(&cmd);
+
+        assert_eq!(summary.runs, 3);
+        assert!(summary.result.is_err());
+    }
+
+    #[test]
+    fn max_runs_caps_iterations_even_without_a_failure() {
+        let cmd = Cmd {
+            max_runs: Some(2),
+            ..base_cmd("true")
+        };
+
+        let summary = run_loop(&cmd);
+
+        assert_eq!(summary.runs, 2);
+        assert!(summary.result.is_ok());
+    }
+
+    #[test]
+    fn fixed_backoff_uses_wait_as_is() {
+        let cmd = Cmd {
+            wait: Some(5),
+            backoff: Backoff::Fixed,
+            ..base_cmd("true")
+        };
+
+        assert_eq!(backoff_duration(&cmd, 1), Some(Duration::from_secs(5)));
+        assert_eq!(backoff_duration(&cmd, 4), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn exponential_backoff_doubles_and_caps_at_max_wait() {
+        let cmd = Cmd {
+            wait: Some(2),
+            backoff: Backoff::Exponential,
+            max_wait: Some(10),
+            ..base_cmd("true")
+        };
+
+        assert_eq!(backoff_duration(&cmd, 1), Some(Duration::from_secs(2)));
+        assert_eq!(backoff_duration(&cmd, 2), Some(Duration::from_secs(4)));
+        assert_eq!(backoff_duration(&cmd, 3), Some(Duration::from_secs(8)));
+        assert_eq!(backoff_duration(&cmd, 4), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn jitter_stays_within_ten_percent_for_a_fixed_seed() {
+        const SEED: u64 = 42;
+
+        for iteration in 1..=20 {
+            let seconds = jitter(100, true, iteration, SEED);
+            assert!(
+                (90..=110).contains(&seconds),
+                "seconds {seconds} out of +/-10% range for iteration {iteration}"
+            );
+            assert_eq!(
+                jitter(100, true, iteration, SEED),
+                seconds,
+                "jitter must be deterministic for a given seed and iteration"
+            );
+        }
+    }
+
+    #[test]
+    fn jitter_differs_across_seeds_so_processes_avoid_lockstep_retries() {
+        let a: Vec<u64> = (1..=10).map(|i| jitter(100, true, i, 1)).collect();
+        let b: Vec<u64> = (1..=10).map(|i| jitter(100, true, i, 2)).collect();
+
+        assert_ne!(a, b);
+    }
+}
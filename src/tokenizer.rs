@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Result};
+
+/// Splits a single shell-like command string into words, honoring POSIX
+/// single-quote rules.
+///
+/// Outside of quotes, runs of non-whitespace characters form a word and
+/// runs of whitespace separate words. A `'` opens a quoted span in which
+/// every character is taken literally until the next `'`. Immediately
+/// after a closing quote, a `\` followed by `\` or `!` is collapsed into
+/// that single literal character (so `'it'\!'s'` yields `it!s`), after
+/// which scanning resumes unquoted. Any other `\` is an ordinary character,
+/// so `'foo'\''bar'` is an unterminated-quote error.
+///
+/// This is intentionally not a full shell grammar: no double quotes, no
+/// variable expansion, no pipes or redirection. Use `RUN_ONE_SYSTEM_SHELL`
+/// if you need real shell semantics.
+pub(crate) fn tokenize(input: &str) -> Result<Vec<String>> {
+    #[derive(PartialEq)]
+    enum State {
+        Unquoted,
+        Quoted,
+        UnquotedEscaped,
+    }
+
+    let chars: Vec<char> = input.chars().collect();
+    let mut state = State::Unquoted;
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match state {
+            State::Unquoted => {
+                if c.is_whitespace() {
+                    if in_word {
+                        words.push(std::mem::take(&mut current));
+                        in_word = false;
+                    }
+                } else if c == '\'' {
+                    state = State::Quoted;
+                    in_word = true;
+                } else {
+                    current.push(c);
+                    in_word = true;
+                }
+            }
+            State::Quoted => {
+                if c == '\'' {
+                    state = State::UnquotedEscaped;
+                } else {
+                    current.push(c);
+                }
+            }
+            State::UnquotedEscaped => {
+                if c == '\\' && i + 1 < chars.len() && matches!(chars[i + 1], '\\' | '!') {
+                    current.push(chars[i + 1]);
+                    i += 1;
+                    state = State::Unquoted;
+                } else {
+                    state = State::Unquoted;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    if state == State::Quoted {
+        return Err(anyhow!("Unterminated quote in shell string: {input:?}"));
+    }
+    if in_word {
+        words.push(current);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_simple_words() {
+        let words = tokenize("echo hello world").unwrap();
+        assert_eq!(words, vec!["echo", "hello", "world"]);
+    }
+
+    #[test]
+    fn collapses_repeated_whitespace() {
+        let words = tokenize("echo   hello\tworld").unwrap();
+        assert_eq!(words, vec!["echo", "hello", "world"]);
+    }
+
+    #[test]
+    fn honors_single_quotes() {
+        let words = tokenize("echo 'foo bar' baz").unwrap();
+        assert_eq!(words, vec!["echo", "foo bar", "baz"]);
+    }
+
+    #[test]
+    fn handles_pipe_as_literal_argument() {
+        let words = tokenize("echo foo | sed 's/foo/bar/g'").unwrap();
+        assert_eq!(
+            words,
+            vec!["echo", "foo", "|", "sed", "s/foo/bar/g"]
+        );
+    }
+
+    #[test]
+    fn escapes_after_closing_quote() {
+        let words = tokenize("'it'\\!'s'").unwrap();
+        assert_eq!(words, vec!["it!s"]);
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        let result = tokenize("echo 'unterminated");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_string_yields_no_words() {
+        let words = tokenize("").unwrap();
+        assert!(words.is_empty());
+    }
+}